@@ -0,0 +1,420 @@
+//! Compiles matrix expressions (see the `expr` module) into a small
+//! stack-based bytecode and persists it to a versioned `.matc` file, so a
+//! pipeline can be parsed once and replayed cheaply. A program is a
+//! newline-separated list of statements using the same grammar as the
+//! REPL: `name = <expr>` and `print <expr>`, where `<expr>` is either
+//! `load <path>` or an expression understood by `expr::parse`.
+
+use ::expr;
+use ::{AnyMatrix, BoxedError, ElementType, Result};
+
+use ::std::collections::HashMap;
+use ::std::fs::File;
+use ::std::io::{Read, Write};
+use ::std::path::Path;
+
+const MAGIC: &'static [u8; 4] = b"MATC";
+// Bumped from 1: constants gained a leading element-type tag byte and
+// widened from 4-byte i32 elements to 8-byte i64/f64.
+const VERSION: u32 = 2;
+
+const OP_LOAD_CONST: u8 = 1;
+const OP_LOAD_VAR: u8 = 2;
+const OP_STORE_VAR: u8 = 3;
+const OP_ADD: u8 = 4;
+const OP_MUL: u8 = 5;
+const OP_TRANSPOSE: u8 = 6;
+const OP_PRINT: u8 = 7;
+
+const ELEM_TYPE_INT: u8 = 0;
+const ELEM_TYPE_FLOAT: u8 = 1;
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    LoadConst(u32),
+    LoadVar(u32),
+    StoreVar(u32),
+    Add,
+    Mul,
+    Transpose,
+    Print,
+}
+
+/// A compiled program: a constant pool of embedded matrices (each its own
+/// `Int`/`Float` variant, just like the one-shot CLI's `AnyMatrix`), the
+/// number of local variable slots it needs, and the opcode stream.
+pub struct Program {
+    num_locals: u32,
+    consts: Vec<AnyMatrix>,
+    ops: Vec<Op>,
+}
+
+struct Compiler {
+    consts: Vec<AnyMatrix>,
+    ops: Vec<Op>,
+    locals: HashMap<String, u32>,
+}
+
+impl Compiler {
+    fn new() -> Self {
+        Compiler { consts: Vec::new(), ops: Vec::new(), locals: HashMap::new() }
+    }
+
+    fn push_const(&mut self, matrix: AnyMatrix) -> u32 {
+        let idx = self.consts.len() as u32;
+        self.consts.push(matrix);
+        idx
+    }
+
+    fn local_slot(&mut self, name: &str) -> u32 {
+        let next = self.locals.len() as u32;
+        *self.locals.entry(name.to_string()).or_insert(next)
+    }
+
+    fn compile_expr(&mut self, expr: &expr::Expr) -> Result<()> {
+        match *expr {
+            expr::Expr::Var(ref name) => {
+                let slot = *self.locals.get(name)
+                    .ok_or_else(|| <BoxedError>::from(format!("Undefined variable: {}", name)))?;
+                self.ops.push(Op::LoadVar(slot));
+            },
+            expr::Expr::Add(ref lhs, ref rhs) => {
+                self.compile_expr(lhs)?;
+                self.compile_expr(rhs)?;
+                self.ops.push(Op::Add);
+            },
+            expr::Expr::Mul(ref lhs, ref rhs) => {
+                self.compile_expr(lhs)?;
+                self.compile_expr(rhs)?;
+                self.ops.push(Op::Mul);
+            },
+            expr::Expr::Transpose(ref inner) => {
+                self.compile_expr(inner)?;
+                self.ops.push(Op::Transpose);
+            },
+        }
+        Ok(())
+    }
+
+    // Compiles the right-hand side of a line: `load <path>` reads the
+    // matrix eagerly (typed via `--type=` if given, else inferred from
+    // the file, same as the one-shot CLI) and embeds it in the constant
+    // pool; anything else is handed to `expr::parse`.
+    fn compile_rhs(&mut self, tokens: &[&str], type_flag: Option<ElementType>) -> Result<()> {
+        if let ["load", path] = tokens {
+            let contents = ::read_file(path)?;
+            let matrix = AnyMatrix::parse(&contents, type_flag.unwrap_or_else(|| ::infer_type(&contents)))?;
+            let idx = self.push_const(matrix);
+            self.ops.push(Op::LoadConst(idx));
+            return Ok(());
+        }
+
+        let source = tokens.join(" ");
+        let parsed = expr::parse(&source).map_err(<BoxedError>::from)?;
+        self.compile_expr(&parsed)
+    }
+
+    fn compile_line(&mut self, line: &str, type_flag: Option<ElementType>) -> Result<()> {
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+
+        match tokens.as_slice() {
+            [] => Ok(()),
+            ["print", rest @ ..] => {
+                self.compile_rhs(rest, type_flag)?;
+                self.ops.push(Op::Print);
+                Ok(())
+            },
+            [name, "=", rest @ ..] => {
+                self.compile_rhs(rest, type_flag)?;
+                let slot = self.local_slot(name);
+                self.ops.push(Op::StoreVar(slot));
+                Ok(())
+            },
+            _ => Err(<BoxedError>::from(format!("Unrecognized command: {}", line))),
+        }
+    }
+}
+
+/// Compiles a whole program into a `Program` ready to save or run.
+/// `type_flag` fixes the element type for every `load` in the program
+/// (mirroring the one-shot CLI's `--type=`); `None` infers it per file.
+pub fn compile(source: &str, type_flag: Option<ElementType>) -> Result<Program> {
+    let mut compiler = Compiler::new();
+    for line in source.lines() {
+        compiler.compile_line(line.trim(), type_flag)?;
+    }
+
+    Ok(Program {
+        num_locals: compiler.locals.len() as u32,
+        consts: compiler.consts,
+        ops: compiler.ops,
+    })
+}
+
+fn write_u8<W: Write>(writer: &mut W, value: u8) -> Result<()> {
+    writer.write_all(&[value]).map_err(<BoxedError>::from)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> Result<()> {
+    writer.write_all(&value.to_be_bytes()).map_err(<BoxedError>::from)
+}
+
+fn write_i64<W: Write>(writer: &mut W, value: i64) -> Result<()> {
+    writer.write_all(&value.to_be_bytes()).map_err(<BoxedError>::from)
+}
+
+fn write_f64<W: Write>(writer: &mut W, value: f64) -> Result<()> {
+    writer.write_all(&value.to_be_bytes()).map_err(<BoxedError>::from)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf).map_err(<BoxedError>::from)?;
+    Ok(buf[0])
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf).map_err(<BoxedError>::from)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_i64<R: Read>(reader: &mut R) -> Result<i64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf).map_err(<BoxedError>::from)?;
+    Ok(i64::from_be_bytes(buf))
+}
+
+fn read_f64<R: Read>(reader: &mut R) -> Result<f64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf).map_err(<BoxedError>::from)?;
+    Ok(f64::from_be_bytes(buf))
+}
+
+/// Writes `program` to `path` as a magic header, a version, the constant
+/// pool (each matrix as an element-type tag, rows, cols, then its
+/// elements), and the opcode stream.
+pub fn save<P: AsRef<Path>>(program: &Program, path: P) -> Result<()> {
+    let mut file = File::create(path).map_err(<BoxedError>::from)?;
+
+    file.write_all(MAGIC).map_err(<BoxedError>::from)?;
+    write_u32(&mut file, VERSION)?;
+    write_u32(&mut file, program.num_locals)?;
+
+    write_u32(&mut file, program.consts.len() as u32)?;
+    for matrix in &program.consts {
+        match *matrix {
+            AnyMatrix::Int(ref matrix) => {
+                write_u8(&mut file, ELEM_TYPE_INT)?;
+                let (rows, cols) = matrix.dims();
+                write_u32(&mut file, rows as u32)?;
+                write_u32(&mut file, cols as u32)?;
+                for row in matrix.rows() {
+                    for &value in row {
+                        write_i64(&mut file, value)?;
+                    }
+                }
+            },
+            AnyMatrix::Float(ref matrix) => {
+                write_u8(&mut file, ELEM_TYPE_FLOAT)?;
+                let (rows, cols) = matrix.dims();
+                write_u32(&mut file, rows as u32)?;
+                write_u32(&mut file, cols as u32)?;
+                for row in matrix.rows() {
+                    for &value in row {
+                        write_f64(&mut file, value)?;
+                    }
+                }
+            },
+        }
+    }
+
+    write_u32(&mut file, program.ops.len() as u32)?;
+    for op in &program.ops {
+        match *op {
+            Op::LoadConst(idx) => { write_u8(&mut file, OP_LOAD_CONST)?; write_u32(&mut file, idx)?; },
+            Op::LoadVar(idx) => { write_u8(&mut file, OP_LOAD_VAR)?; write_u32(&mut file, idx)?; },
+            Op::StoreVar(idx) => { write_u8(&mut file, OP_STORE_VAR)?; write_u32(&mut file, idx)?; },
+            Op::Add => write_u8(&mut file, OP_ADD)?,
+            Op::Mul => write_u8(&mut file, OP_MUL)?,
+            Op::Transpose => write_u8(&mut file, OP_TRANSPOSE)?,
+            Op::Print => write_u8(&mut file, OP_PRINT)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a `.matc` file previously written by `save`, checking the magic
+/// header and version.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Program> {
+    let mut file = File::open(path).map_err(<BoxedError>::from)?;
+
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).map_err(<BoxedError>::from)?;
+    if &magic != MAGIC {
+        return Err(<BoxedError>::from("Not a matc file (bad magic)"));
+    }
+
+    let version = read_u32(&mut file)?;
+    if version != VERSION {
+        return Err(<BoxedError>::from(format!("Unsupported matc version: {}", version)));
+    }
+
+    let num_locals = read_u32(&mut file)?;
+
+    let num_consts = read_u32(&mut file)?;
+    let mut consts = Vec::with_capacity(num_consts as usize);
+    for _ in 0..num_consts {
+        let elem_type = read_u8(&mut file)?;
+        let rows = read_u32(&mut file)? as usize;
+        let cols = read_u32(&mut file)? as usize;
+
+        let matrix = match elem_type {
+            ELEM_TYPE_INT => {
+                let mut data = Vec::with_capacity(rows * cols);
+                for _ in 0..(rows * cols) {
+                    data.push(read_i64(&mut file)?);
+                }
+                let matrix = if rows == 0 {
+                    ::matrix::Matrix::new()
+                } else {
+                    ::matrix::Matrix::from_1d(rows, data).map_err(<BoxedError>::from)?
+                };
+                AnyMatrix::Int(matrix)
+            },
+            ELEM_TYPE_FLOAT => {
+                let mut data = Vec::with_capacity(rows * cols);
+                for _ in 0..(rows * cols) {
+                    data.push(read_f64(&mut file)?);
+                }
+                let matrix = if rows == 0 {
+                    ::matrix::Matrix::new()
+                } else {
+                    ::matrix::Matrix::from_1d(rows, data).map_err(<BoxedError>::from)?
+                };
+                AnyMatrix::Float(matrix)
+            },
+            other => return Err(<BoxedError>::from(format!("Unknown element type tag: {}", other))),
+        };
+        consts.push(matrix);
+    }
+
+    let num_ops = read_u32(&mut file)?;
+    let mut ops = Vec::with_capacity(num_ops as usize);
+    for _ in 0..num_ops {
+        let op = match read_u8(&mut file)? {
+            OP_LOAD_CONST => Op::LoadConst(read_u32(&mut file)?),
+            OP_LOAD_VAR => Op::LoadVar(read_u32(&mut file)?),
+            OP_STORE_VAR => Op::StoreVar(read_u32(&mut file)?),
+            OP_ADD => Op::Add,
+            OP_MUL => Op::Mul,
+            OP_TRANSPOSE => Op::Transpose,
+            OP_PRINT => Op::Print,
+            other => return Err(<BoxedError>::from(format!("Unknown opcode: {}", other))),
+        };
+        ops.push(op);
+    }
+
+    Ok(Program { num_locals, consts, ops })
+}
+
+/// Runs a compiled program on a small stack VM, printing each `print`
+/// statement's result via the existing `Display` impl and surfacing
+/// dimension-mismatch and type-mismatch errors as they occur.
+pub fn run(program: &Program) -> Result<()> {
+    let mut stack: Vec<AnyMatrix> = Vec::new();
+    let mut locals: Vec<Option<AnyMatrix>> = vec![None; program.num_locals as usize];
+
+    for op in &program.ops {
+        match *op {
+            Op::LoadConst(idx) => {
+                let matrix = program.consts.get(idx as usize)
+                    .cloned()
+                    .ok_or_else(|| <BoxedError>::from(format!("Bad constant index: {}", idx)))?;
+                stack.push(matrix);
+            },
+            Op::LoadVar(idx) => {
+                let matrix = locals.get(idx as usize)
+                    .and_then(|slot| slot.clone())
+                    .ok_or_else(|| <BoxedError>::from(format!("Uninitialized variable slot: {}", idx)))?;
+                stack.push(matrix);
+            },
+            Op::StoreVar(idx) => {
+                let matrix = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let slot = locals.get_mut(idx as usize)
+                    .ok_or_else(|| <BoxedError>::from(format!("Bad variable slot: {}", idx)))?;
+                *slot = Some(matrix);
+            },
+            Op::Add => {
+                let rhs = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let lhs = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let result = match (lhs, rhs) {
+                    (AnyMatrix::Int(a), AnyMatrix::Int(b)) => (a + b).map(AnyMatrix::Int).map_err(<BoxedError>::from),
+                    (AnyMatrix::Float(a), AnyMatrix::Float(b)) => (a + b).map(AnyMatrix::Float).map_err(<BoxedError>::from),
+                    _ => Err(<BoxedError>::from("Matrix types do not match (mixed --type)")),
+                };
+                stack.push(result?);
+            },
+            Op::Mul => {
+                let rhs = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let lhs = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let result = match (lhs, rhs) {
+                    (AnyMatrix::Int(a), AnyMatrix::Int(b)) => a.dot(b).map(AnyMatrix::Int).map_err(<BoxedError>::from),
+                    (AnyMatrix::Float(a), AnyMatrix::Float(b)) => a.dot(b).map(AnyMatrix::Float).map_err(<BoxedError>::from),
+                    _ => Err(<BoxedError>::from("Matrix types do not match (mixed --type)")),
+                };
+                stack.push(result?);
+            },
+            Op::Transpose => {
+                let matrix = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                let transposed = match matrix {
+                    AnyMatrix::Int(m) => AnyMatrix::Int(m.transpose()),
+                    AnyMatrix::Float(m) => AnyMatrix::Float(m.transpose()),
+                };
+                stack.push(transposed);
+            },
+            Op::Print => {
+                let matrix = stack.pop().ok_or_else(|| <BoxedError>::from("Stack underflow"))?;
+                println!("{}", matrix);
+            },
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_save_load_run_round_trip() {
+        let dir = ::std::env::temp_dir();
+        let data_path = dir.join("os_matrix_bytecode_test_input.txt");
+        let matc_path = dir.join("os_matrix_bytecode_test_program.matc");
+        ::std::fs::write(&data_path, "1 2\n3 4\n").unwrap();
+
+        let source = format!("A = load {}\nprint A\n", data_path.display());
+        let program = compile(&source, None).unwrap();
+        assert_eq!(program.num_locals, 1);
+        assert_eq!(program.consts.len(), 1);
+        assert_eq!(program.ops.len(), 4); // LoadConst+StoreVar for the assignment, LoadVar+Print for the print
+
+        save(&program, &matc_path).unwrap();
+        let loaded = load(&matc_path).unwrap();
+
+        assert_eq!(loaded.num_locals, program.num_locals);
+        assert_eq!(loaded.consts.len(), program.consts.len());
+        assert_eq!(loaded.ops.len(), program.ops.len());
+        match loaded.consts[0] {
+            AnyMatrix::Int(ref matrix) => assert_eq!(matrix.dims(), (2, 2)),
+            AnyMatrix::Float(_) => panic!("expected an Int constant"),
+        }
+
+        run(&loaded).unwrap();
+
+        ::std::fs::remove_file(&data_path).ok();
+        ::std::fs::remove_file(&matc_path).ok();
+    }
+}
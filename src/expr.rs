@@ -0,0 +1,175 @@
+//! A small expression language for chaining matrix operations, e.g.
+//! `A + B * C'`. Precedence (tightest first): postfix `'` (transpose),
+//! `*` (multiply/dot), `+` (add). Parentheses override precedence.
+
+use ::matrix::{Matrix, Matrixable};
+use ::std::collections::HashMap;
+use ::std::fmt;
+
+#[derive(Debug)]
+pub struct Error(String);
+
+impl Error { fn new(string: &str) -> Error { Error(String::from(string)) } }
+
+impl ::std::error::Error for Error {
+    fn description(&self) -> &str { self.0.as_str() }
+    fn cause(&self) -> Option<&::std::error::Error> { None }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<::matrix::Error> for Error {
+    fn from(error: ::matrix::Error) -> Error { Error(error.to_string()) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Plus,
+    Star,
+    Quote,
+    LParen,
+    RParen,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&ch) = chars.peek() {
+        match ch {
+            c if c.is_whitespace() => { chars.next(); },
+            '+' => { chars.next(); tokens.push(Token::Plus); },
+            '*' => { chars.next(); tokens.push(Token::Star); },
+            '\'' => { chars.next(); tokens.push(Token::Quote); },
+            '(' => { chars.next(); tokens.push(Token::LParen); },
+            ')' => { chars.next(); tokens.push(Token::RParen); },
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || c == '_' {
+                        ident.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            },
+            _ => return Err(Error::new(&format!("Unexpected character: {}", ch))),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// An expression AST node. `Var` holds a variable name to be resolved
+/// against an environment at evaluation time.
+#[derive(Debug)]
+pub enum Expr {
+    Var(String),
+    Add(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Transpose(Box<Expr>),
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> { self.tokens.get(self.pos) }
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // expr := term ('+' term)*
+    fn parse_expr(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_term()?;
+        while let Some(&Token::Plus) = self.peek() {
+            self.next();
+            let rhs = self.parse_term()?;
+            lhs = Expr::Add(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // term := postfix ('*' postfix)*
+    fn parse_term(&mut self) -> Result<Expr, Error> {
+        let mut lhs = self.parse_postfix()?;
+        while let Some(&Token::Star) = self.peek() {
+            self.next();
+            let rhs = self.parse_postfix()?;
+            lhs = Expr::Mul(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // postfix := primary '\''*
+    fn parse_postfix(&mut self) -> Result<Expr, Error> {
+        let mut expr = self.parse_primary()?;
+        while let Some(&Token::Quote) = self.peek() {
+            self.next();
+            expr = Expr::Transpose(Box::new(expr));
+        }
+        Ok(expr)
+    }
+
+    // primary := Ident | '(' expr ')'
+    fn parse_primary(&mut self) -> Result<Expr, Error> {
+        match self.next() {
+            Some(Token::Ident(name)) => Ok(Expr::Var(name)),
+            Some(Token::LParen) => {
+                let expr = self.parse_expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(expr),
+                    other => Err(Error::new(&format!("Expected ')', found {:?}", other))),
+                }
+            },
+            other => Err(Error::new(&format!("Unexpected token: {:?}", other))),
+        }
+    }
+}
+
+/// Parses a full expression, requiring every token to be consumed.
+pub fn parse(input: &str) -> Result<Expr, Error> {
+    let tokens = tokenize(input)?;
+    let num_tokens = tokens.len();
+    let mut parser = Parser { tokens, pos: 0 };
+
+    let expr = parser.parse_expr()?;
+    if parser.pos != num_tokens {
+        return Err(Error::new(&format!(
+            "Unexpected trailing token: {:?}", parser.tokens[parser.pos]
+        )));
+    }
+    Ok(expr)
+}
+
+/// Walks the AST, resolving variables against `env` and calling the
+/// existing `Matrix::add`/`Matrix::dot`/`Matrix::transpose`.
+pub fn eval<T: Matrixable>(expr: &Expr, env: &HashMap<String, Matrix<T>>) -> Result<Matrix<T>, Error> {
+    match *expr {
+        Expr::Var(ref name) => env.get(name)
+            .cloned()
+            .ok_or_else(|| Error::new(&format!("Undefined variable: {}", name))),
+        Expr::Add(ref lhs, ref rhs) => {
+            let lhs = eval(lhs, env)?;
+            let rhs = eval(rhs, env)?;
+            (lhs + rhs).map_err(Error::from)
+        },
+        Expr::Mul(ref lhs, ref rhs) => {
+            let lhs = eval(lhs, env)?;
+            let rhs = eval(rhs, env)?;
+            lhs.dot(rhs).map_err(Error::from)
+        },
+        Expr::Transpose(ref inner) => Ok(eval(inner, env)?.transpose()),
+    }
+}
@@ -1,80 +1,277 @@
 extern crate num_traits;
 
+mod bytecode;
+mod expr;
 mod matrix;
 
+use std::collections::HashMap;
 use std::convert::From;
 use std::env::args;
 use std::fs::File;
-use std::io::{Write, Read};
+use std::io::{BufRead, Write, Read};
 use std::path::Path;
+use std::str::FromStr;
 
 const USAGE_TEXT: &'static str = "Usage: matrix operation [source1 [source2]]\n";
 const STDIN_FILENAME: &'static str = "/dev/stdin";
+const REPL_PROMPT: &'static str = "> ";
 
 type BoxedError = Box<std::error::Error>;
 type Result<T> = std::result::Result<T, BoxedError>;
-type NumberType = i32;
-type Matrix = matrix::Matrix<NumberType>;
 
 fn usage() -> ! {
     std::io::stderr().write(USAGE_TEXT.as_bytes()).unwrap();
     std::process::exit(1);
 }
 
-fn matrix_from_file<P: AsRef<Path>>(path: P) -> Result<Matrix> {
+fn read_file<P: AsRef<Path>>(path: P) -> Result<String> {
     let mut contents = String::new();
     File::open(path)
         .map_err(<BoxedError>::from)?
         .read_to_string(&mut contents)
         .map_err(<BoxedError>::from)?;
 
-    contents.parse::<Matrix>()
+    Ok(contents)
 }
 
-fn print_add(m1: Matrix, m2: Matrix) { println!("{}", (m1 + m2).unwrap()); }
-fn print_dot(m1: Matrix, m2: Matrix) { println!("{}", m1.dot(m2).unwrap()); }
+/// The element type backing a one-shot CLI invocation, chosen at runtime
+/// via `--type=` or inferred from the input.
+#[derive(Clone, Copy, PartialEq)]
+enum ElementType { Int, Float }
 
-fn print_transpose(matrix: Matrix) { println!("{}", matrix); }
+/// Dispatches CLI operations over either an integer or floating-point
+/// matrix, since `Matrix<T>` is monomorphic but the element type is only
+/// known at runtime.
+#[derive(Clone)]
+enum AnyMatrix {
+    Int(matrix::Matrix<i64>),
+    Float(matrix::Matrix<f64>),
+}
+
+impl AnyMatrix {
+    fn parse(contents: &str, element_type: ElementType) -> Result<Self> {
+        match element_type {
+            ElementType::Int => contents.parse::<matrix::Matrix<i64>>().map(AnyMatrix::Int),
+            ElementType::Float => contents.parse::<matrix::Matrix<f64>>().map(AnyMatrix::Float),
+        }
+    }
+}
 
-fn print_dims(matrix: Matrix) {
-    let (rows, cols) = matrix.dims();
+impl std::fmt::Display for AnyMatrix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            AnyMatrix::Int(ref matrix) => write!(f, "{}", matrix),
+            AnyMatrix::Float(ref matrix) => write!(f, "{}", matrix),
+        }
+    }
+}
+
+// Looks for a `--type=i64`/`--type=f64` argument; `None` means the type
+// should be inferred from the input instead.
+fn parse_type_flag<'a, I: Iterator<Item=&'a String>>(args: I) -> Option<ElementType> {
+    for arg in args {
+        if let Some(name) = arg.strip_prefix("--type=") {
+            return Some(match name {
+                "i64" => ElementType::Int,
+                "f64" => ElementType::Float,
+                other => {
+                    eprintln!("Unknown --type '{}', expected i64 or f64", other);
+                    std::process::exit(1);
+                },
+            });
+        }
+    }
+    None
+}
+
+// Infers the element type from the input when `--type=` isn't given: any
+// decimal point means the data is floating-point.
+fn infer_type(contents: &str) -> ElementType {
+    if contents.contains('.') { ElementType::Float } else { ElementType::Int }
+}
+
+fn print_add(m1: AnyMatrix, m2: AnyMatrix) {
+    let result = match (m1, m2) {
+        (AnyMatrix::Int(a), AnyMatrix::Int(b)) => (a + b).map(AnyMatrix::Int).map_err(<BoxedError>::from),
+        (AnyMatrix::Float(a), AnyMatrix::Float(b)) => (a + b).map(AnyMatrix::Float).map_err(<BoxedError>::from),
+        _ => Err(<BoxedError>::from("Matrix types do not match (mixed --type)")),
+    };
+    println!("{}", result.unwrap());
+}
+
+fn print_dot(m1: AnyMatrix, m2: AnyMatrix) {
+    let result = match (m1, m2) {
+        (AnyMatrix::Int(a), AnyMatrix::Int(b)) => a.dot(b).map(AnyMatrix::Int).map_err(<BoxedError>::from),
+        (AnyMatrix::Float(a), AnyMatrix::Float(b)) => a.dot(b).map(AnyMatrix::Float).map_err(<BoxedError>::from),
+        _ => Err(<BoxedError>::from("Matrix types do not match (mixed --type)")),
+    };
+    println!("{}", result.unwrap());
+}
+
+fn print_transpose(matrix: AnyMatrix) { println!("{}", matrix); }
+
+fn print_dims(matrix: AnyMatrix) {
+    let (rows, cols) = match matrix {
+        AnyMatrix::Int(ref matrix) => matrix.dims(),
+        AnyMatrix::Float(ref matrix) => matrix.dims(),
+    };
     println!("{} {}", rows, cols);
 }
 
-fn print_means(matrix: Matrix) {
-    println!(
-        "{}",
-        matrix
+fn print_means(matrix: AnyMatrix) {
+    let means = match matrix {
+        AnyMatrix::Int(ref matrix) => matrix
+            .column_means()
+            .iter()
+            .map(i64::to_string)
+            .collect::<Vec<String>>(),
+        AnyMatrix::Float(ref matrix) => matrix
             .column_means()
             .iter()
-            .map(NumberType::to_string)
-            .collect::<Vec<String>>()
-            .as_slice()
-            .join("\t")
-    );
+            .map(f64::to_string)
+            .collect::<Vec<String>>(),
+    };
+    println!("{}", means.as_slice().join("\t"));
+}
+
+fn print_repl_prompt() {
+    print!("{}", REPL_PROMPT);
+    std::io::stdout().flush().unwrap();
+}
+
+// Evaluates the right-hand side of a REPL line. Tries `load <path>` and
+// chunk0-1's original word-based grammar (`<var>`, `<var> transpose`,
+// `<lhs> add|multiply <rhs>`) first, then falls back to the `expr` module's
+// symbolic grammar (`A + B * C'`) — both stay usable side by side.
+fn eval_repl_expr<T>(tokens: &[&str], env: &HashMap<String, matrix::Matrix<T>>) -> Result<matrix::Matrix<T>>
+where T: matrix::Matrixable + FromStr, T::Err: 'static + std::error::Error {
+    let lookup = |name: &str| -> Result<matrix::Matrix<T>> {
+        env.get(name)
+            .cloned()
+            .ok_or_else(|| <BoxedError>::from(format!("Undefined variable: {}", name)))
+    };
+
+    match tokens {
+        ["load", path] => return read_file(path)?.parse::<matrix::Matrix<T>>(),
+        [name, "transpose"] => return lookup(name).map(|matrix| matrix.transpose()),
+        [lhs, "add", rhs] => return (lookup(lhs)? + lookup(rhs)?).map_err(<BoxedError>::from),
+        [lhs, "multiply", rhs] => return lookup(lhs)?.dot(lookup(rhs)?).map_err(<BoxedError>::from),
+        _ => {},
+    }
+
+    let source = tokens.join(" ");
+    let expr = expr::parse(&source).map_err(<BoxedError>::from)?;
+    expr::eval(&expr, env).map_err(<BoxedError>::from)
+}
+
+// Evaluates one REPL line, returning a matrix to print for `print ...`
+// expressions, or nothing for assignments.
+fn eval_repl_line<T>(line: &str, env: &mut HashMap<String, matrix::Matrix<T>>) -> Result<Option<matrix::Matrix<T>>>
+where T: matrix::Matrixable + FromStr, T::Err: 'static + std::error::Error {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+
+    match tokens.as_slice() {
+        [] => Ok(None),
+        ["print", rest @ ..] => eval_repl_expr(rest, env).map(Some),
+        [name, "=", rest @ ..] => {
+            let matrix = eval_repl_expr(rest, env)?;
+            env.insert(name.to_string(), matrix);
+            Ok(None)
+        },
+        _ => Err(<BoxedError>::from(format!("Unrecognized command: {}", line))),
+    }
+}
+
+// Runs the REPL loop over a single chosen element type `T` for the whole
+// session (the REPL's variable environment can't mix element types the
+// way the one-shot CLI's `AnyMatrix` can).
+fn repl_typed<T>()
+where T: matrix::Matrixable + FromStr + ToString, T::Err: 'static + std::error::Error {
+    let mut env: HashMap<String, matrix::Matrix<T>> = HashMap::new();
+    let stdin = std::io::stdin();
+
+    print_repl_prompt();
+    for line in stdin.lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match eval_repl_line(line.trim(), &mut env) {
+            Ok(Some(matrix)) => println!("{}", matrix),
+            Ok(None) => {},
+            Err(error) => eprintln!("Error: {}", error),
+        }
+        print_repl_prompt();
+    }
+}
+
+// Dispatches to a `repl_typed` instantiation for the chosen `--type=`
+// (defaulting to `i64`, matching the pre-chunk0-4 integer-only behavior
+// when no flag is given).
+fn repl(type_flag: Option<ElementType>) {
+    match type_flag.unwrap_or(ElementType::Int) {
+        ElementType::Int => repl_typed::<i64>(),
+        ElementType::Float => repl_typed::<f64>(),
+    }
 }
 
 fn main() {
-    let mode = match args().nth(1) { 
-        Some(mode) => mode,
+    let all_args: Vec<String> = args().collect();
+    let type_flag = parse_type_flag(all_args.iter());
+    let positional: Vec<&str> = all_args.iter()
+        .skip(1)
+        .filter(|arg| !arg.starts_with("--type="))
+        .map(String::as_str)
+        .collect();
+
+    let mode = match positional.first() {
+        Some(&mode) => mode,
         None => usage(),
     };
 
-    let matrix_1 = matrix_from_file(
-        args().nth(2).unwrap_or(String::from(STDIN_FILENAME))
+    if mode == "repl" {
+        repl(type_flag);
+        return;
+    }
+
+    if mode == "compile" {
+        let program_path = positional.get(1).expect("Usage: matrix compile <program> <out.matc>");
+        let out_path = positional.get(2).expect("Usage: matrix compile <program> <out.matc>");
+        let source = read_file(program_path).expect("Error reading program");
+        let program = bytecode::compile(&source, type_flag).expect("Error compiling program");
+        bytecode::save(&program, out_path).expect("Error writing bytecode");
+        return;
+    }
+
+    if mode == "run" {
+        let matc_path = positional.get(1).expect("Usage: matrix run <file.matc>");
+        let program = bytecode::load(matc_path).expect("Error loading bytecode");
+        bytecode::run(&program).expect("Error running bytecode");
+        return;
+    }
+
+    let contents_1 = read_file(
+        positional.get(1).cloned().unwrap_or(STDIN_FILENAME)
+    ).expect("Error reading matrix 1");
+    let matrix_1 = AnyMatrix::parse(
+        &contents_1, type_flag.unwrap_or_else(|| infer_type(&contents_1))
     ).expect("Error parsing matrix 1");
 
-    let matrix_2_option = args().nth(3)
-        .map(matrix_from_file)
-        .map(|result| result.expect("Error parsing matrix 2"));
+    let matrix_2_option = positional.get(2)
+        .map(|&path| read_file(path).expect("Error reading matrix 2"))
+        .map(|contents| {
+            AnyMatrix::parse(&contents, type_flag.unwrap_or_else(|| infer_type(&contents)))
+                .expect("Error parsing matrix 2")
+        });
 
-    match mode.as_str() {
+    match mode {
         "dims"             => print_dims(matrix_1),
         "transpose"        => print_transpose(matrix_1),
         "mean"             => print_means(matrix_1),
         "add" | "multiply" => {
             let matrix_2 = matrix_2_option.expect("Matrix 2 not provided");
-            match mode.as_str() {
+            match mode {
                 "add"      => print_add(matrix_1, matrix_2),
                 "multiply" => print_dot(matrix_1, matrix_2),
                 _          => unreachable!(),
@@ -1,7 +1,5 @@
 #![allow(dead_code)]
 
-use ::num_traits::cast::AsPrimitive;
-
 use ::std::iter::Sum;
 use ::std::ops::{Add, Mul, Div};
 use ::std::str::FromStr;
@@ -47,6 +45,16 @@ pub struct Matrix<T>{
     data: Vec<T>,
 }
 
+impl<T: Matrixable> Clone for Matrix<T> {
+    fn clone(&self) -> Self {
+        Self {
+            num_rows: self.num_rows,
+            num_cols: self.num_cols,
+            data: self.data.clone(),
+        }
+    }
+}
+
 impl<T: Matrixable> Matrix<T> {
     pub fn new() -> Self { Default::default() }
     pub fn from_1d(num_rows: usize, data: Vec<T>) -> Result<Self, Error> {
@@ -125,23 +133,185 @@ impl<T: Matrixable> Matrix<T> {
             data: new_data
         })
     }
-    
+
+}
+
+impl<T: Matrixable + ::num_traits::Float> Matrix<T> {
+    // Row-reduces `augmented` (an n-row buffer with `n` pivot columns
+    // followed by whatever is being carried along, e.g. an identity or a
+    // right-hand side) to reduced row-echelon form via Gauss-Jordan
+    // elimination with partial pivoting. Returns an error if a pivot is
+    // ~0, i.e. the leading `n` columns are singular.
+    fn gauss_jordan(mut augmented: Vec<Vec<T>>, n: usize) -> Result<Vec<Vec<T>>, Error> {
+        for k in 0..n {
+            let pivot_row = (k..n)
+                .max_by(|&a, &b| augmented[a][k].abs().partial_cmp(&augmented[b][k].abs()).unwrap())
+                .unwrap();
+
+            if augmented[pivot_row][k].abs() < T::epsilon() {
+                return Err(Error::new("Matrix is singular"));
+            }
+
+            augmented.swap(k, pivot_row);
+
+            let pivot = augmented[k][k];
+            for value in augmented[k].iter_mut() {
+                *value = *value / pivot;
+            }
+
+            let (before, from_k) = augmented.split_at_mut(k);
+            let (pivot_row, after) = from_k.split_first_mut().unwrap();
+
+            for row in before.iter_mut().chain(after.iter_mut()) {
+                let factor = row[k];
+                for (cell, pivot_value) in row.iter_mut().zip(pivot_row.iter()) {
+                    *cell = *cell - factor * *pivot_value;
+                }
+            }
+        }
+
+        Ok(augmented)
+    }
+
+    /// Computes the determinant via Gaussian elimination with partial
+    /// pivoting, tracking the sign flips from row swaps. Errors if the
+    /// matrix isn't square.
+    pub fn determinant(&self) -> Result<T, Error> {
+        if self.num_rows != self.num_cols {
+            return Err(Error::new(&format!(
+                "Matrix must be square to compute a determinant ({}x{})",
+                self.num_rows, self.num_cols,
+            )));
+        }
+
+        let n = self.num_rows;
+        let mut buffer: Vec<Vec<T>> = self.rows().map(|row| row.to_vec()).collect();
+        let mut sign = T::one();
+
+        for k in 0..n {
+            let pivot_row = (k..n)
+                .max_by(|&a, &b| buffer[a][k].abs().partial_cmp(&buffer[b][k].abs()).unwrap())
+                .unwrap();
+
+            if buffer[pivot_row][k].abs() < T::epsilon() {
+                return Err(Error::new("Matrix is singular"));
+            }
+
+            if pivot_row != k {
+                buffer.swap(k, pivot_row);
+                sign = -sign;
+            }
+
+            let (top, bottom) = buffer.split_at_mut(k + 1);
+            let pivot_row = &top[k];
+            for row in bottom.iter_mut() {
+                let factor = row[k] / pivot_row[k];
+                // Columns before k are already ~0 from prior eliminations,
+                // so zipping the whole row (not just k..n) is a harmless
+                // no-op there.
+                for (cell, pivot_value) in row.iter_mut().zip(pivot_row.iter()) {
+                    *cell = *cell - factor * *pivot_value;
+                }
+            }
+        }
+
+        let diagonal_product = (0..n)
+            .map(|i| buffer[i][i])
+            .fold(T::one(), |acc, x| acc * x);
+
+        Ok(sign * diagonal_product)
+    }
+
+    /// Computes the inverse by running Gauss-Jordan elimination on the
+    /// augmented matrix `[A | I]`. Errors if the matrix isn't square or
+    /// is singular.
+    pub fn inverse(&self) -> Result<Self, Error> {
+        if self.num_rows != self.num_cols {
+            return Err(Error::new(&format!(
+                "Matrix must be square to invert ({}x{})",
+                self.num_rows, self.num_cols,
+            )));
+        }
+
+        let n = self.num_rows;
+        let augmented: Vec<Vec<T>> = self.rows().enumerate()
+            .map(|(i, row)| {
+                let mut row = row.to_vec();
+                row.extend((0..n).map(|j| if i == j { T::one() } else { T::zero() }));
+                row
+            })
+            .collect();
+
+        let reduced = Self::gauss_jordan(augmented, n)?;
+
+        Self::from_2d(
+            reduced.into_iter()
+                .map(|row| row[n..].to_vec())
+                .collect()
+        )
+    }
+
+    /// Solves `Ax = b` by running Gauss-Jordan elimination on the
+    /// augmented matrix `[A | b]`. Errors if `A` isn't square, `b`'s row
+    /// count doesn't match, or `A` is singular.
+    pub fn solve(&self, b: &Self) -> Result<Self, Error> {
+        if self.num_rows != self.num_cols {
+            return Err(Error::new(&format!(
+                "Matrix must be square to solve ({}x{})",
+                self.num_rows, self.num_cols,
+            )));
+        }
+        if self.num_rows != b.num_rows {
+            return Err(Error::new(&format!(
+                "Incompatible dimensions ({} != {})",
+                self.num_rows, b.num_rows,
+            )));
+        }
+
+        let n = self.num_rows;
+        let augmented: Vec<Vec<T>> = self.rows().zip(b.rows())
+            .map(|(a_row, b_row)| {
+                let mut row = a_row.to_vec();
+                row.extend_from_slice(b_row);
+                row
+            })
+            .collect();
+
+        let reduced = Self::gauss_jordan(augmented, n)?;
+
+        Self::from_2d(
+            reduced.into_iter()
+                .map(|row| row[n..].to_vec())
+                .collect()
+        )
+    }
 }
 
-impl<T: Matrixable + AsPrimitive<isize>> Matrix<T> where isize: AsPrimitive<T> {
-    pub fn column_means(&self) -> Vec<T> {
+impl Matrix<i64> {
+    pub fn column_means(&self) -> Vec<i64> {
         self
             .transpose()
             .rows()
             .map(|row| {
-                let sum: isize = row
-                    .iter()
-                    .cloned()
-                    .map(AsPrimitive::<isize>::as_)
-                    .sum();
-                AsPrimitive::<T>::as_(sum / row.len() as isize)
+                let sum: i64 = row.iter().cloned().sum();
+                sum / row.len() as i64
             })
-            .collect::<Vec<T>>()
+            .collect::<Vec<i64>>()
+    }
+}
+
+impl Matrix<f64> {
+    // Unlike the integer path, division here is exact float division
+    // rather than truncating sum/len.
+    pub fn column_means(&self) -> Vec<f64> {
+        self
+            .transpose()
+            .rows()
+            .map(|row| {
+                let sum: f64 = row.iter().cloned().sum();
+                sum / row.len() as f64
+            })
+            .collect::<Vec<f64>>()
     }
 }
 
@@ -202,7 +372,37 @@ where <T as FromStr>::Err: 'static + ::std::error::Error {
             .map(Iterator::collect::<Result<_, _>>)
             .collect::<Result<_, _>>()
             .map_err(Self::Err::from)?;
-        
+
         Self::from_2d(data).map_err(Self::Err::from)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn determinant_of_known_matrix() {
+        let m = Matrix::from_2d(vec![vec![2.0, 1.0], vec![1.0, 1.0]]).unwrap();
+        assert_eq!(m.determinant().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn determinant_of_singular_matrix_is_an_error() {
+        let m = Matrix::from_2d(vec![vec![1.0, 2.0], vec![2.0, 4.0]]).unwrap();
+        assert!(m.determinant().is_err());
+    }
+
+    #[test]
+    fn inverse_of_known_matrix() {
+        let m = Matrix::from_2d(vec![vec![2.0, 1.0], vec![1.0, 1.0]]).unwrap();
+        assert_eq!(m.inverse().unwrap().to_string(), "1\t-1\n-1\t2\n");
+    }
+
+    #[test]
+    fn solve_known_system() {
+        let a = Matrix::from_2d(vec![vec![2.0, 1.0], vec![1.0, 1.0]]).unwrap();
+        let b = Matrix::from_2d(vec![vec![3.0], vec![2.0]]).unwrap();
+        assert_eq!(a.solve(&b).unwrap().to_string(), "1\n1\n");
+    }
+}